@@ -1,4 +1,9 @@
-use std::{collections::HashMap, fmt, str::FromStr};
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
 
 use anyhow::Ok;
 use futures_lite::StreamExt;
@@ -8,9 +13,18 @@ use iroh_gossip::{
     net::{Event, Gossip, GossipEvent, GossipReceiver, GossipSender},
     proto::TopicId,
 };
+use operational_transform::OperationSeq;
 use serde::{Deserialize, Serialize};
 
 use crate::async_runtime::AsyncRuntime;
+use crate::broadcast_throttle::BroadcastThrottle;
+use crate::chat_history::ChatHistory;
+use crate::ot_document::SharedDocument;
+use crate::voice::{VOICE_ALPN, VoiceFrame, VoiceSession};
+
+/// Default location for the chat history database, relative to the
+/// running game's working directory.
+const HISTORY_DB_PATH: &str = "chat_history.sqlite3";
 
 type SendData = i32;
 
@@ -57,6 +71,18 @@ impl FromStr for Ticket {
 enum Message {
     AboutMe { from: NodeId, name: String },
     Message { from: NodeId, text: String },
+    /// `rev` is the index of `ops` within `from`'s own local edit stream
+    /// (`0` for their first local edit, `1` for their second, ...), used
+    /// by [`crate::ot_document::SharedDocument::apply_remote`] to apply
+    /// each peer's ops in the order that peer generated them.
+    Edit { from: NodeId, rev: u64, ops: OperationSeq },
+    Rpc {
+        from: NodeId,
+        kind: String,
+        id: u64,
+        payload: Vec<u8>,
+        reply_to: Option<u64>,
+    },
 }
 
 impl Message {
@@ -69,37 +95,158 @@ impl Message {
     }
 }
 
+/// Wraps `text` as a `Message::Message`, broadcasts it, persists it to
+/// history, and echoes it locally, same as the unthrottled path always did.
+/// Shared by the immediate send path and the throttled flush so both stay
+/// in sync.
+async fn broadcast_chat_message(
+    sender: &GossipSender,
+    history: &ChatHistory,
+    print_sender: &tokio::sync::mpsc::Sender<String>,
+    topic: TopicId,
+    from: NodeId,
+    from_name: &str,
+    text: &str,
+) {
+    let message = Message::Message {
+        from,
+        text: text.to_string(),
+    };
+    sender.broadcast(message.to_vec().into()).await.unwrap();
+    if let Err(err) = history.record(topic, from, from_name, text) {
+        print_sender
+            .send(format!("! failed to persist message: {err}"))
+            .await
+            .unwrap();
+    }
+    println!("> sent: {text}");
+}
+
+/// A change in who is reachable on a topic, reported up to `process` so it
+/// can turn into `peer_joined`/`peer_left` signals.
+enum PeerEvent {
+    Joined { node_id: NodeId, name: String },
+    Left { node_id: NodeId },
+}
+
+/// An RPC message that arrived over gossip, forwarded up to `process` so
+/// registered handlers only ever run on the Godot thread.
+struct IncomingRpc {
+    from: NodeId,
+    kind: String,
+    id: u64,
+    payload: Vec<u8>,
+    reply_to: Option<u64>,
+}
+
+/// An RPC message `process` wants the room's spawned task to broadcast:
+/// either a fresh call to `kind`, or a reply correlated to an incoming
+/// call's `id`.
+enum RpcOutgoing {
+    Call { kind: String, id: u64, payload: Vec<u8> },
+    Reply { id: u64, payload: Vec<u8> },
+}
+
 // Handle incoming events
 async fn subscribe_loop(
     mut receiver: GossipReceiver,
     message_tx: tokio::sync::mpsc::Sender<String>,
+    peer_tx: tokio::sync::mpsc::Sender<PeerEvent>,
+    peers: Arc<Mutex<HashMap<NodeId, String>>>,
+    voice: VoiceSession,
+    history: ChatHistory,
+    document: Arc<Mutex<SharedDocument>>,
+    document_tx: tokio::sync::mpsc::Sender<String>,
+    rpc_tx: tokio::sync::mpsc::Sender<IncomingRpc>,
+    topic: TopicId,
 ) -> Result<(), anyhow::Error> {
     // keep track of the mapping between `NodeId`s and names
     let mut names = HashMap::new();
     // iterate over all events
     while let Some(event) = receiver.try_next().await? {
-        // if the Event is a `GossipEvent::Received`, let's deserialize the message:
-        if let Event::Gossip(GossipEvent::Received(msg)) = event {
-            // deserialize the message and match on the
-            // message type:
-            match Message::from_bytes(&msg.content)? {
-                Message::AboutMe { from, name } => {
-                    // if it's an `AboutMe` message
-                    // add and entry into the map
-                    // and print the name
-                    names.insert(from, name.clone());
-                    message_tx
-                        .send(format!("> {} is now known as {}", from.fmt_short(), name))
-                        .await?;
-                }
-                Message::Message { from, text } => {
-                    // if it's a `Message` message,
-                    // get the name from the map
-                    // and print the message
+        let Event::Gossip(gossip_event) = event else {
+            continue;
+        };
+        match gossip_event {
+            GossipEvent::Joined(node_ids) => {
+                for node_id in node_ids {
                     let name = names
-                        .get(&from)
-                        .map_or_else(|| from.fmt_short(), String::to_string);
-                    message_tx.send(format!("{}: {}", name, text)).await?;
+                        .get(&node_id)
+                        .cloned()
+                        .unwrap_or_else(|| node_id.fmt_short());
+                    peers.lock().unwrap().insert(node_id, name.clone());
+                    voice.connect_peer(node_id);
+                    peer_tx.send(PeerEvent::Joined { node_id, name }).await?;
+                }
+            }
+            GossipEvent::NeighborUp(node_id) => {
+                let name = names
+                    .get(&node_id)
+                    .cloned()
+                    .unwrap_or_else(|| node_id.fmt_short());
+                peers.lock().unwrap().insert(node_id, name.clone());
+                voice.connect_peer(node_id);
+                peer_tx.send(PeerEvent::Joined { node_id, name }).await?;
+            }
+            GossipEvent::NeighborDown(node_id) => {
+                peers.lock().unwrap().remove(&node_id);
+                peer_tx.send(PeerEvent::Left { node_id }).await?;
+            }
+            GossipEvent::Received(msg) => {
+                // deserialize the message and match on the
+                // message type:
+                match Message::from_bytes(&msg.content)? {
+                    Message::AboutMe { from, name } => {
+                        // if it's an `AboutMe` message
+                        // add and entry into the map
+                        // and print the name
+                        names.insert(from, name.clone());
+                        peers.lock().unwrap().insert(from, name.clone());
+                        peer_tx
+                            .send(PeerEvent::Joined {
+                                node_id: from,
+                                name: name.clone(),
+                            })
+                            .await?;
+                        message_tx
+                            .send(format!("> {} is now known as {}", from.fmt_short(), name))
+                            .await?;
+                    }
+                    Message::Message { from, text } => {
+                        // if it's a `Message` message,
+                        // get the name from the map
+                        // and print the message
+                        let name = names
+                            .get(&from)
+                            .map_or_else(|| from.fmt_short(), String::to_string);
+                        if let Err(err) = history.record(topic, from, &name, &text) {
+                            message_tx
+                                .send(format!("! failed to persist message: {err}"))
+                                .await?;
+                        }
+                        message_tx.send(format!("{}: {}", name, text)).await?;
+                    }
+                    Message::Edit { from, rev, ops } => {
+                        // a remote edit: apply it in the causal order `from`
+                        // generated it in, transformed against whatever
+                        // local edits we haven't confirmed `from` has seen
+                        let text = {
+                            let mut document = document.lock().unwrap();
+                            if let Err(err) = document.apply_remote(from, rev, ops) {
+                                message_tx
+                                    .send(format!("! failed to apply remote edit: {err}"))
+                                    .await?;
+                                continue;
+                            }
+                            document.text().to_string()
+                        };
+                        document_tx.send(text).await?;
+                    }
+                    Message::Rpc { from, kind, id, payload, reply_to } => {
+                        rpc_tx
+                            .send(IncomingRpc { from, kind, id, payload, reply_to })
+                            .await?;
+                    }
                 }
             }
         }
@@ -107,52 +254,172 @@ async fn subscribe_loop(
     Ok(())
 }
 
+/// Everything a single joined/opened gossip topic needs to keep running and
+/// to be polled from `process`. One of these exists per entry in
+/// `AsyncSingleton::rooms`.
+struct RoomHandle {
+    topic: TopicId,
+    ticket_string: GString,
+    remote_message_receiver: tokio::sync::mpsc::Receiver<String>,
+    ticket_receiver: tokio::sync::mpsc::Receiver<String>,
+    peer_event_receiver: tokio::sync::mpsc::Receiver<PeerEvent>,
+    voice_frame_receiver: tokio::sync::mpsc::Receiver<VoiceFrame>,
+    document_receiver: tokio::sync::mpsc::Receiver<String>,
+    document_sender: tokio::sync::mpsc::Sender<String>,
+    edit_sender: tokio::sync::mpsc::Sender<(u64, OperationSeq)>,
+    rpc_event_receiver: tokio::sync::mpsc::Receiver<IncomingRpc>,
+    rpc_out_sender: tokio::sync::mpsc::Sender<RpcOutgoing>,
+    user_input_sender: tokio::sync::mpsc::Sender<String>,
+    voice: VoiceSession,
+    throttle: BroadcastThrottle,
+    /// Live roster for this room, kept up to date by `subscribe_loop` and
+    /// read back out by `get_peers`.
+    peers: Arc<Mutex<HashMap<NodeId, String>>>,
+    /// Shared text buffer for this room, kept in sync via operational
+    /// transform. Read/written from the Godot thread by `apply_local_edit`
+    /// and from the room's spawned task by `subscribe_loop`.
+    document: Arc<Mutex<SharedDocument>>,
+}
+
 #[derive(GodotClass)]
 #[class(base=Node)]
 pub struct AsyncSingleton {
     base: Base<Node>,
-    ticket_string: GString,
     name: Option<GString>,
-    remote_message_receiver: Option<tokio::sync::mpsc::Receiver<String>>,
-    ticket_receiver: Option<tokio::sync::mpsc::Receiver<String>>,
+    print_sender: tokio::sync::mpsc::Sender<String>,
     print_receiver: Option<tokio::sync::mpsc::Receiver<String>>,
-    user_input_sender: Option<tokio::sync::mpsc::Sender<String>>,
+    rooms: HashMap<String, RoomHandle>,
+    history: ChatHistory,
+    /// Registered `call_remote` dispatch table, keyed by `kind`. Global
+    /// rather than per-room since a game only ever needs one handler per
+    /// message kind, regardless of which room it arrives on.
+    handlers: HashMap<String, Callable>,
+    /// Monotonically increasing id handed out by `call_remote`, so replies
+    /// can be correlated back to the call that requested them.
+    rpc_id_counter: u64,
+    /// Monotonically increasing id mixed into `room_id`, so joining the
+    /// same topic twice (e.g. re-joining a room we already left, or
+    /// opening two independent sessions on one topic) gets two distinct
+    /// `rooms` entries instead of the second `create_room` silently
+    /// overwriting the first.
+    room_id_counter: u64,
 }
 
 #[godot_api]
 impl INode for AsyncSingleton {
     fn init(base: Base<Node>) -> Self {
+        // shared across every room so `process` only ever has to drain one
+        // debug log, no matter how many topics we end up in
+        let (print_sender, print_receiver) = tokio::sync::mpsc::channel::<String>(32);
+        let history = ChatHistory::open(HISTORY_DB_PATH, None)
+            .expect("failed to open chat history database");
         Self {
             base,
-            ticket_string: "".into(),
             name: None,
-            remote_message_receiver: None,
-            ticket_receiver: None,
-            print_receiver: None,
-            user_input_sender: None,
+            print_sender,
+            print_receiver: Some(print_receiver),
+            rooms: HashMap::new(),
+            history,
+            handlers: HashMap::new(),
+            rpc_id_counter: 0,
+            room_id_counter: 0,
         }
     }
 
     fn ready(&mut self) {}
 
+    fn exit_tree(&mut self) {
+        // same teardown as `leave_chat`, so quitting the game doesn't
+        // dangle iroh endpoints/sockets behind it
+        self.rooms.clear();
+    }
+
     fn process(&mut self, delta: f64) {
         let mut self_gd = self.to_gd();
-        if let Some(receiver) = &mut self.remote_message_receiver {
-            while let Some(value) = receiver.try_recv().ok() {
-                self_gd.signals()
+        for (room_id, room) in self.rooms.iter_mut() {
+            while let Some(value) = room.remote_message_receiver.try_recv().ok() {
+                self_gd
+                    .signals()
                     .message_received()
-                    .emit(GString::from(value));
+                    .emit(GString::from(room_id.as_str()), GString::from(value));
             }
-        }
 
-        if let Some(receiver) = &mut self.ticket_receiver {
-            while let Some(value) = receiver.try_recv().ok() {
+            while let Some(value) = room.ticket_receiver.try_recv().ok() {
                 let ticket_string = GString::from(value);
-                self.ticket_string = ticket_string.clone();
+                room.ticket_string = ticket_string.clone();
                 self_gd
                     .signals()
                     .ticket_received()
-                    .emit(ticket_string);
+                    .emit(GString::from(room_id.as_str()), ticket_string);
+            }
+
+            while let Some(event) = room.peer_event_receiver.try_recv().ok() {
+                match event {
+                    PeerEvent::Joined { node_id, name } => {
+                        self_gd.signals().peer_joined().emit(
+                            GString::from(room_id.as_str()),
+                            GString::from(node_id.to_string()),
+                            GString::from(name),
+                        );
+                    }
+                    PeerEvent::Left { node_id } => {
+                        self_gd.signals().peer_left().emit(
+                            GString::from(room_id.as_str()),
+                            GString::from(node_id.to_string()),
+                        );
+                    }
+                }
+            }
+
+            while let Some(frame) = room.voice_frame_receiver.try_recv().ok() {
+                self_gd.signals().voice_frame_received().emit(
+                    GString::from(room_id.as_str()),
+                    GString::from(frame.from.to_string()),
+                    PackedFloat32Array::from(frame.samples.as_slice()),
+                );
+            }
+
+            while let Some(text) = room.document_receiver.try_recv().ok() {
+                self_gd
+                    .signals()
+                    .document_changed()
+                    .emit(GString::from(room_id.as_str()), GString::from(text));
+            }
+
+            while let Some(rpc) = room.rpc_event_receiver.try_recv().ok() {
+                match rpc.reply_to {
+                    // a reply to a call we made: hand it straight back via signal
+                    Some(reply_to) => {
+                        self_gd.signals().rpc_reply().emit(
+                            GString::from(room_id.as_str()),
+                            reply_to as i64,
+                            PackedByteArray::from(rpc.payload.as_slice()),
+                        );
+                    }
+                    // a fresh call: dispatch to whatever handler registered for
+                    // this kind, and broadcast its return value back as a reply
+                    // if it gave us one
+                    None => {
+                        if let Some(handler) = self.handlers.get(&rpc.kind) {
+                            let payload = PackedByteArray::from(rpc.payload.as_slice());
+                            let result = handler.call(&[payload.to_variant()]);
+                            if !result.is_nil() {
+                                let reply_payload: PackedByteArray = result.to();
+                                let sender = room.rpc_out_sender.clone();
+                                let id = rpc.id;
+                                AsyncRuntime::spawn(async move {
+                                    sender
+                                        .send(RpcOutgoing::Reply {
+                                            id,
+                                            payload: reply_payload.to_vec(),
+                                        })
+                                        .await
+                                        .unwrap();
+                                });
+                            }
+                        }
+                    }
+                }
             }
         }
 
@@ -169,10 +436,25 @@ impl AsyncSingleton {
     pub const SINGLETON: &'static str = "AsyncEventBus";
 
     #[signal]
-    fn message_received(message: GString);
+    fn message_received(room_id: GString, message: GString);
+
+    #[signal]
+    fn ticket_received(room_id: GString, ticket: GString);
+
+    #[signal]
+    fn peer_joined(room_id: GString, node_id: GString, name: GString);
+
+    #[signal]
+    fn peer_left(room_id: GString, node_id: GString);
+
+    #[signal]
+    fn voice_frame_received(room_id: GString, node_id: GString, samples: PackedFloat32Array);
+
+    #[signal]
+    fn document_changed(room_id: GString, new_text: GString);
 
     #[signal]
-    fn ticket_received(message: GString);
+    fn rpc_reply(room_id: GString, id: i64, payload: PackedByteArray);
 
     #[func]
     pub fn hello(&self) {
@@ -180,51 +462,278 @@ impl AsyncSingleton {
     }
 
     #[func]
-    pub fn get_ticket(&mut self) -> GString {
-        self.ticket_string.clone()
+    pub fn get_ticket(&self, room_id: GString) -> GString {
+        self.rooms
+            .get(&room_id.to_string())
+            .map(|room| room.ticket_string.clone())
+            .unwrap_or_default()
     }
 
     #[func]
-    pub fn open_async_chat(&mut self) {
+    pub fn open_async_chat(&mut self) -> GString {
         let topic = TopicId::from_bytes(rand::random());
 
-        self.start_gossip(topic, vec![]);
+        GString::from(self.create_room(topic, vec![]))
     }
 
     #[func]
-    pub fn join_async_chat(&mut self, ticket: GString) {
+    pub fn join_room(&mut self, ticket: GString) -> GString {
         godot_print!("Joining async chat with ticket: {}", ticket);
         let Ticket { topic, nodes } = Ticket::from_str(&ticket.to_string()).unwrap();
 
-        self.start_gossip(topic, nodes);
+        GString::from(self.create_room(topic, nodes))
     }
 
-    fn start_gossip(&mut self, topic: TopicId, nodes: Vec<NodeAddr>) {
+    #[func]
+    pub fn leave_room(&mut self, room_id: GString) {
+        // dropping the handle drops `user_input_sender`, which closes the
+        // spawned room's input channel and lets its broadcast loop end and
+        // reach `router.shutdown()`
+        if self.rooms.remove(&room_id.to_string()).is_none() {
+            godot_print!("No such room: {}", room_id);
+        }
+    }
+
+    /// Leaves every joined/opened room at once, the "player shutdown" path
+    /// for when the whole chat session (not just one room) is ending.
+    #[func]
+    pub fn leave_chat(&mut self) {
+        self.rooms.clear();
+    }
+
+    #[func]
+    pub fn send_to_room(&self, room_id: GString, message: GString) {
+        let Some(room) = self.rooms.get(&room_id.to_string()) else {
+            godot_print!("No such room: {}", room_id);
+            return;
+        };
+        let sender = room.user_input_sender.clone();
+        let text = message.to_string();
+        AsyncRuntime::spawn(async move {
+            sender.send(text).await.unwrap();
+        });
+    }
+
+    /// Encodes one 20ms/48kHz mono frame captured from the microphone and
+    /// ships it to every peer we already have a voice stream open with in
+    /// `room_id`.
+    #[func]
+    pub fn push_voice_frame(&self, room_id: GString, samples: PackedFloat32Array) {
+        let Some(room) = self.rooms.get(&room_id.to_string()) else {
+            godot_print!("No such room: {}", room_id);
+            return;
+        };
+        let voice = room.voice.clone();
+        let pcm = samples.to_vec();
+        AsyncRuntime::spawn(async move {
+            if let Err(err) = voice.push_frame(&pcm).await {
+                godot_print!("Failed to send voice frame: {}", err);
+            }
+        });
+    }
+
+    /// Paces `room_id`'s outgoing chat broadcasts instead of sending every
+    /// line the moment it's typed/pushed. `messages_per_second <= 0.0`
+    /// disables throttling. `coalesce` batches every message queued inside
+    /// one flush window into a single payload when `true`, or keeps only
+    /// the latest one when `false` (for per-frame state pushes where only
+    /// the newest value matters).
+    #[func]
+    pub fn set_broadcast_rate(&self, room_id: GString, messages_per_second: f64, coalesce: bool) {
+        let Some(room) = self.rooms.get(&room_id.to_string()) else {
+            godot_print!("No such room: {}", room_id);
+            return;
+        };
+        room.throttle.set_rate(messages_per_second, coalesce);
+    }
+
+    /// Applies a locally authored operational-transform op (`retain` chars,
+    /// then insert `insert`, then delete `delete` chars — `retain + delete`
+    /// must equal the document's current length) to `room_id`'s shared
+    /// document and broadcasts it for every other peer to apply.
+    #[func]
+    pub fn apply_local_edit(&self, room_id: GString, retain: i64, insert: GString, delete: i64) {
+        let Some(room) = self.rooms.get(&room_id.to_string()) else {
+            godot_print!("No such room: {}", room_id);
+            return;
+        };
+
+        let mut op = OperationSeq::default();
+        if retain > 0 {
+            op.retain(retain as u64);
+        }
+        let insert = insert.to_string();
+        if !insert.is_empty() {
+            op.insert(&insert);
+        }
+        if delete > 0 {
+            op.delete(delete as u64);
+        }
+
+        let rev = {
+            let mut document = room.document.lock().unwrap();
+            let rev = match document.apply_local(op.clone()) {
+                Ok(rev) => rev,
+                Err(err) => {
+                    godot_print!("Failed to apply local edit: {}", err);
+                    return;
+                }
+            };
+            if let Err(err) = room.document_sender.try_send(document.text().to_string()) {
+                godot_print!("Failed to queue document_changed update: {}", err);
+            }
+            rev
+        };
+
+        let edit_sender = room.edit_sender.clone();
+        AsyncRuntime::spawn(async move {
+            edit_sender.send((rev, op)).await.unwrap();
+        });
+    }
+
+    /// Returns the currently known participants of `room_id` as
+    /// `"<node_id> <display name>"` entries, for a live roster UI.
+    #[func]
+    pub fn get_peers(&self, room_id: GString) -> Array<GString> {
+        let mut array = Array::new();
+        if let Some(room) = self.rooms.get(&room_id.to_string()) {
+            for (node_id, name) in room.peers.lock().unwrap().iter() {
+                array.push(&GString::from(format!("{} {}", node_id, name)));
+            }
+        }
+        array
+    }
+
+    /// Backfills scrollback for `room_id` (the id returned by
+    /// `open_async_chat`/`join_room`, same as every other per-room `#[func]`
+    /// here) so the Godot UI can show history immediately after joining,
+    /// even before this node has seen any live traffic on the room's topic.
+    #[func]
+    pub fn load_history(&self, room_id: GString, limit: i64) -> Array<GString> {
+        let mut array = Array::new();
+        let Some(room) = self.rooms.get(&room_id.to_string()) else {
+            godot_print!("No such room: {}", room_id);
+            return array;
+        };
+        match self.history.load(room.topic, limit) {
+            Ok(lines) => {
+                for line in lines {
+                    array.push(&GString::from(line));
+                }
+            }
+            Err(err) => godot_print!("Failed to load chat history: {}", err),
+        }
+        array
+    }
+
+    /// Caps how many rows of history are retained per topic. Pass a
+    /// non-positive value to keep everything.
+    #[func]
+    pub fn set_history_retention_limit(&mut self, limit: i64) {
+        self.history
+            .set_retention_limit(if limit > 0 { Some(limit) } else { None });
+    }
+
+    /// Registers `handler` to run whenever a `call_remote` of `kind` arrives
+    /// from a peer. Called from `process`, on the Godot thread, so `handler`
+    /// is free to touch the scene tree. If it returns a non-nil value, that
+    /// value is broadcast back as a correlated reply.
+    #[func]
+    pub fn register_handler(&mut self, kind: GString, handler: Callable) {
+        self.handlers.insert(kind.to_string(), handler);
+    }
+
+    /// Broadcasts an RPC of `kind` carrying `payload` to `room_id` and
+    /// returns a request id. If a peer's handler replies, that id comes back
+    /// via `rpc_reply`.
+    #[func]
+    pub fn call_remote(&mut self, room_id: GString, kind: GString, payload: PackedByteArray) -> i64 {
+        let Some(room) = self.rooms.get(&room_id.to_string()) else {
+            godot_print!("No such room: {}", room_id);
+            return -1;
+        };
+        let sender = room.rpc_out_sender.clone();
+
+        self.rpc_id_counter += 1;
+        let id = self.rpc_id_counter;
+        let kind = kind.to_string();
+        let payload = payload.to_vec();
+        AsyncRuntime::spawn(async move {
+            sender
+                .send(RpcOutgoing::Call { kind, id, payload })
+                .await
+                .unwrap();
+        });
+        id as i64
+    }
+
+    /// Joins (or creates, for a fresh topic) a gossip room, registers its
+    /// `RoomHandle`, and spawns the task that drives the `Endpoint`/`Gossip`/
+    /// `Router` for it. Returns the new room id.
+    ///
+    /// The room id is *not* just the topic: joining the same topic twice
+    /// (two independent `join_room`/`open_async_chat` calls on one topic)
+    /// must produce two independent `rooms` entries rather than the second
+    /// `insert` silently dropping the first room's handle, so a counter is
+    /// mixed into the id to keep it unique per call.
+    fn create_room(&mut self, topic: TopicId, nodes: Vec<NodeAddr>) -> String {
+        let room_id = format!("{topic}-{}", self.room_id_counter);
+        self.room_id_counter += 1;
+
         // create a multi-provider, single-consumer channel
         let (remote_message_tx, remote_message_rx) = tokio::sync::mpsc::channel::<String>(1);
-        self.remote_message_receiver = Some(remote_message_rx);
         let (input_tx, mut input_rx) = tokio::sync::mpsc::channel::<String>(1);
-        self.user_input_sender = Some(input_tx);
-        let (print_sender, print_receiver) = tokio::sync::mpsc::channel::<String>(32);
-
-        self.print_receiver = Some(print_receiver);
-
+        let (ticket_tx, ticket_rx) = tokio::sync::mpsc::channel::<String>(1);
+        let (peer_tx, peer_event_rx) = tokio::sync::mpsc::channel::<PeerEvent>(32);
+        let (voice_frame_tx, voice_frame_rx) = tokio::sync::mpsc::channel::<VoiceFrame>(32);
+        let (document_tx, document_rx) = tokio::sync::mpsc::channel::<String>(8);
+        let (edit_tx, mut edit_rx) = tokio::sync::mpsc::channel::<(u64, OperationSeq)>(8);
+        let (rpc_tx, rpc_event_rx) = tokio::sync::mpsc::channel::<IncomingRpc>(32);
+        let (rpc_out_tx, mut rpc_out_rx) = tokio::sync::mpsc::channel::<RpcOutgoing>(32);
+        let peers = Arc::new(Mutex::new(HashMap::new()));
+        let document = Arc::new(Mutex::new(SharedDocument::new()));
+        let voice = VoiceSession::new(voice_frame_tx).expect("failed to initialize Opus codec");
+        let throttle = BroadcastThrottle::unthrottled();
+
+        self.rooms.insert(
+            room_id.clone(),
+            RoomHandle {
+                topic,
+                ticket_string: "".into(),
+                remote_message_receiver: remote_message_rx,
+                ticket_receiver: ticket_rx,
+                peer_event_receiver: peer_event_rx,
+                voice_frame_receiver: voice_frame_rx,
+                document_receiver: document_rx,
+                document_sender: document_tx.clone(),
+                edit_sender: edit_tx,
+                rpc_event_receiver: rpc_event_rx,
+                rpc_out_sender: rpc_out_tx,
+                user_input_sender: input_tx,
+                voice: voice.clone(),
+                peers: peers.clone(),
+                document: document.clone(),
+                throttle: throttle.clone(),
+            },
+        );
+
+        let print_sender = self.print_sender.clone();
+        let history = self.history.clone();
         let name = match &self.name {
             Some(name) => Some(name.to_string()),
             None => None,
         };
 
-        let (ticket_tx, ticket_rx) = tokio::sync::mpsc::channel::<String>(1);
-        self.ticket_receiver = Some(ticket_rx);
-
         AsyncRuntime::spawn(async move {
             let endpoint = Endpoint::builder().discovery_n0().bind().await.unwrap();
+            voice.set_endpoint(endpoint.clone());
 
             print_sender.send(format!("> our node id: {}", endpoint.node_id())).await.unwrap();
             let gossip = Gossip::builder().spawn(endpoint.clone()).await.unwrap();
 
             let router = Router::builder(endpoint.clone())
                 .accept(iroh_gossip::ALPN, gossip.clone())
+                .accept(VOICE_ALPN, voice.clone())
                 .spawn()
                 .await
                 .unwrap();
@@ -260,6 +769,11 @@ impl AsyncSingleton {
                 .split();
             print_sender.send(format!("> connected!")).await.unwrap();
 
+            // what `broadcast_chat_message` should persist/print ourselves
+            // as, mirroring how `subscribe_loop` resolves a remote sender's
+            // display name: our configured name, or our short node id
+            let own_name = name.clone().unwrap_or_else(|| endpoint.node_id().fmt_short());
+
             // broadcast our name, if set
             if let Some(name) = name {
                 let message = Message::AboutMe {
@@ -270,51 +784,114 @@ impl AsyncSingleton {
             }
 
             // subscribe and print loop
-            tokio::spawn(subscribe_loop(receiver, remote_message_tx));
+            let subscribe_task = tokio::spawn(subscribe_loop(
+                receiver,
+                remote_message_tx,
+                peer_tx,
+                peers,
+                voice.clone(),
+                history.clone(),
+                document,
+                document_tx,
+                rpc_tx,
+                topic,
+            ));
 
             // broadcast each line we type
             print_sender.send(format!("> type a message and hit enter to broadcast...")).await.unwrap();
-            // listen for lines that we have typed to be sent from `stdin`
-            while let Some(text) = input_rx.recv().await {
-                // create a message from the text
-                let message = Message::Message {
-                    from: endpoint.node_id(),
-                    text: text.clone(),
+            // listen for lines typed locally and for local document edits.
+            // this loop ends on its own once `leave_room`/`leave_chat` drops
+            // our half of the input channel, or `exit_tree` drops every room
+            // as the game quits
+            let mut latest_pending: Option<String> = None;
+            let mut batched_pending: Vec<String> = Vec::new();
+            // the flush deadline itself, so a sustained fast producer (the
+            // per-frame `physics_process` state push this throttle exists
+            // to tame) can't keep pushing it back out: re-creating a fresh
+            // `sleep(interval)` on every loop iteration would mean every
+            // `input_rx`/`edit_rx`/`rpc_out_rx` branch that fires before it
+            // elapses restarts the deadline from "now", so it never fires
+            // under sustained load. This is only reset once the flush it's
+            // counting down to actually happens (or throttling is turned
+            // off), not on every loop iteration.
+            let mut next_flush: Option<tokio::time::Instant> = None;
+            loop {
+                let throttle_interval = throttle.interval();
+                match throttle_interval {
+                    Some(interval) if next_flush.is_none() => {
+                        next_flush = Some(tokio::time::Instant::now() + interval);
+                    }
+                    None => next_flush = None,
+                    Some(_) => {}
+                }
+                let flush_timer = async {
+                    match next_flush {
+                        Some(deadline) => tokio::time::sleep_until(deadline).await,
+                        // no rate configured: never fires, so the branch
+                        // below is effectively disabled
+                        None => std::future::pending::<()>().await,
+                    }
                 };
-                // broadcast the encoded message
-                sender.broadcast(message.to_vec().into()).await.unwrap();
-                // print to ourselves the text that we sent
-                println!("> sent: {text}");
+                tokio::select! {
+                    text = input_rx.recv() => {
+                        let Some(text) = text else { break };
+                        if throttle_interval.is_none() {
+                            // unthrottled (the default): send immediately
+                            broadcast_chat_message(&sender, &history, &print_sender, topic, endpoint.node_id(), &own_name, &text).await;
+                        } else if throttle.coalesce() {
+                            batched_pending.push(text);
+                        } else {
+                            latest_pending = Some(text);
+                        }
+                    }
+                    edit = edit_rx.recv() => {
+                        let Some((rev, ops)) = edit else { continue };
+                        let message = Message::Edit { from: endpoint.node_id(), rev, ops };
+                        sender.broadcast(message.to_vec().into()).await.unwrap();
+                    }
+                    rpc_out = rpc_out_rx.recv() => {
+                        let Some(rpc_out) = rpc_out else { continue };
+                        let message = match rpc_out {
+                            RpcOutgoing::Call { kind, id, payload } => Message::Rpc {
+                                from: endpoint.node_id(),
+                                kind,
+                                id,
+                                payload,
+                                reply_to: None,
+                            },
+                            RpcOutgoing::Reply { id, payload } => Message::Rpc {
+                                from: endpoint.node_id(),
+                                kind: String::new(),
+                                id,
+                                payload,
+                                reply_to: Some(id),
+                            },
+                        };
+                        sender.broadcast(message.to_vec().into()).await.unwrap();
+                    }
+                    _ = flush_timer, if next_flush.is_some() => {
+                        next_flush = None;
+                        if throttle.coalesce() {
+                            if !batched_pending.is_empty() {
+                                let batched = batched_pending.join("\n");
+                                batched_pending.clear();
+                                broadcast_chat_message(&sender, &history, &print_sender, topic, endpoint.node_id(), &own_name, &batched).await;
+                            }
+                        } else if let Some(text) = latest_pending.take() {
+                            broadcast_chat_message(&sender, &history, &print_sender, topic, endpoint.node_id(), &own_name, &text).await;
+                        }
+                    }
+                }
             }
+
+            // we've been asked to leave: drop our sender half to unsubscribe
+            // from the topic, stop the receive loop, and tear down the
+            // router/endpoint instead of leaking them
+            drop(sender);
+            subscribe_task.abort();
             router.shutdown().await.unwrap();
         });
-    }
 
-    #[func]
-    pub fn poll_receiver(&mut self) -> Array<GString> {
-        let mut array = Array::new();
-        if let Some(receiver) = &mut self.remote_message_receiver {
-            while let Some(value) = receiver.try_recv().ok() {
-                //godot_print!("Received value: {}", value);
-                let message = GString::from(value);
-                array.push(&message);
-            }
-        } else {
-            godot_print!("Receiver is not initialized!");
-        }
-        array
-    }
-
-    #[func]
-    pub fn send_message(&self, message: GString) {
-        let string = message.to_string();
-        let sender = self.user_input_sender.clone();
-        if sender.is_none() {
-            godot_print!("Sender is not initialized!");
-            return;
-        }
-        AsyncRuntime::spawn(async {
-            sender.unwrap().send(string).await.unwrap();
-        });
+        room_id
     }
 }
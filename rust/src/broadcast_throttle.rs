@@ -0,0 +1,52 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Optional pacing for a room's outgoing broadcasts. Defaults to
+/// unthrottled (every message goes out immediately, same as before this
+/// existed); `set_rate` switches it to a token-bucket-style minimum
+/// interval that coalesces whatever arrived inside one window before the
+/// timed flush.
+#[derive(Clone)]
+pub struct BroadcastThrottle {
+    state: Arc<Mutex<ThrottleState>>,
+}
+
+#[derive(Clone, Copy)]
+struct ThrottleState {
+    interval: Option<Duration>,
+    coalesce: bool,
+}
+
+impl BroadcastThrottle {
+    pub fn unthrottled() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ThrottleState {
+                interval: None,
+                coalesce: false,
+            })),
+        }
+    }
+
+    /// `messages_per_second <= 0.0` disables throttling again. `coalesce`
+    /// picks how queued messages are combined at each flush: `true` batches
+    /// every message from the window into one framed payload (for chat
+    /// lines, where every line matters), `false` keeps only the latest (for
+    /// per-frame state updates, where only the newest value matters).
+    pub fn set_rate(&self, messages_per_second: f64, coalesce: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.interval = if messages_per_second > 0.0 {
+            Some(Duration::from_secs_f64(1.0 / messages_per_second))
+        } else {
+            None
+        };
+        state.coalesce = coalesce;
+    }
+
+    pub fn interval(&self) -> Option<Duration> {
+        self.state.lock().unwrap().interval
+    }
+
+    pub fn coalesce(&self) -> bool {
+        self.state.lock().unwrap().coalesce
+    }
+}
@@ -0,0 +1,100 @@
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use iroh::NodeId;
+use iroh_gossip::proto::TopicId;
+use rusqlite::{Connection, params};
+
+/// Local SQLite-backed log of every chat line that crosses a gossip topic,
+/// so a peer that joins late (or restarts) can backfill scrollback instead
+/// of only ever seeing messages sent after it connected.
+///
+/// Cheap to clone: the connection is shared behind an `Arc<Mutex<_>>` so
+/// every room's spawned task can hold its own handle.
+#[derive(Clone)]
+pub struct ChatHistory {
+    conn: Arc<Mutex<Connection>>,
+    retention_limit: Arc<Mutex<Option<i64>>>,
+}
+
+impl ChatHistory {
+    /// Opens (and migrates, if needed) the SQLite database at `path`.
+    /// `retention_limit` caps how many rows are kept per topic; `None`
+    /// means keep everything.
+    pub fn open(path: &str, retention_limit: Option<i64>) -> Result<Self, anyhow::Error> {
+        let conn = Connection::open(path)?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS messages (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                topic TEXT NOT NULL,
+                node_id TEXT NOT NULL,
+                display_name TEXT NOT NULL,
+                sent_at INTEGER NOT NULL,
+                text TEXT NOT NULL
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS messages_topic_id ON messages (topic, id)",
+            [],
+        )?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            retention_limit: Arc::new(Mutex::new(retention_limit)),
+        })
+    }
+
+    pub fn set_retention_limit(&self, retention_limit: Option<i64>) {
+        *self.retention_limit.lock().unwrap() = retention_limit;
+    }
+
+    /// Persists one chat line for `topic` and, if a retention limit is
+    /// configured, trims that topic's oldest rows back down to it.
+    /// `display_name` is whatever `subscribe_loop` would print the sender
+    /// as at the time the message arrived (their `AboutMe` name, or
+    /// `fmt_short()` if they hadn't sent one yet), so `load` can reproduce
+    /// the same line later.
+    pub fn record(
+        &self,
+        topic: TopicId,
+        node_id: NodeId,
+        display_name: &str,
+        text: &str,
+    ) -> Result<(), anyhow::Error> {
+        let topic = topic.to_string();
+        let sent_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO messages (topic, node_id, display_name, sent_at, text) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![topic, node_id.to_string(), display_name, sent_at, text],
+        )?;
+        if let Some(limit) = *self.retention_limit.lock().unwrap() {
+            conn.execute(
+                "DELETE FROM messages WHERE topic = ?1 AND id NOT IN (
+                    SELECT id FROM messages WHERE topic = ?1 ORDER BY id DESC LIMIT ?2
+                )",
+                params![topic, limit],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Loads up to `limit` of the most recent lines for `topic`, oldest
+    /// first, formatted the same way `subscribe_loop` prints live messages.
+    pub fn load(&self, topic: TopicId, limit: i64) -> Result<Vec<String>, anyhow::Error> {
+        let topic = topic.to_string();
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT display_name, text FROM messages WHERE topic = ?1 ORDER BY id DESC LIMIT ?2",
+        )?;
+        let mut rows = stmt.query(params![topic, limit])?;
+        let mut lines = Vec::new();
+        while let Some(row) = rows.next()? {
+            let display_name: String = row.get(0)?;
+            let text: String = row.get(1)?;
+            lines.push(format!("{}: {}", display_name, text));
+        }
+        lines.reverse();
+        Ok(lines)
+    }
+}
@@ -3,7 +3,11 @@ use async_runtime::AsyncRuntime;
 use godot::{classes::Engine, prelude::*};
 mod async_runtime;
 mod async_event_bus;
+mod broadcast_throttle;
+mod chat_history;
+mod ot_document;
 mod player;
+mod voice;
 
 struct MyExtension;
 
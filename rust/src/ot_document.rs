@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use iroh::NodeId;
+use operational_transform::OperationSeq;
+
+/// Bounds how many unacknowledged local ops we keep around to transform
+/// incoming remote ops against. A peer this far behind needs a fresh
+/// snapshot rather than incremental ops, so we just drop the oldest ones.
+const MAX_UNACKED: usize = 64;
+
+/// Bounds how many out-of-causal-order remote ops we'll hold per peer while
+/// waiting for the op(s) that precede them to arrive. Gossip gives no
+/// delivery ordering between peers, so a peer's ops can arrive out of the
+/// order they generated them in; a peer this far ahead of what we've been
+/// able to apply needs a fresh snapshot rather than incremental ops, so any
+/// further ops from it are dropped on arrival instead of buffered, the same
+/// trade-off as `MAX_UNACKED`.
+const MAX_PENDING_PER_PEER: usize = 64;
+
+/// A single peer's view of a shared text buffer, kept in sync with every
+/// other peer on the topic via operational transform: local edits apply
+/// immediately, and remote edits are transformed against our own local
+/// edits before being applied.
+///
+/// There's no central sequencer and gossip carries no acks, so this can't
+/// know exactly which of our local ops a given remote op is concurrent
+/// with - it conservatively transforms every incoming remote op against
+/// every op still in `unacked`, which keeps ops from one peer's stream
+/// causally ordered and stops gossip's reordering from silently dropping
+/// edits, but (unlike a true vector-clock/central-sequencer scheme) isn't
+/// a strict CRDT: it trades a little extra transform work for "never
+/// lose an edit" rather than proving minimal-transform convergence.
+pub struct SharedDocument {
+    text: String,
+    revision: u64,
+    /// Count of ops *we've* applied locally, used to stamp outgoing
+    /// `Message::Edit`s with our index into our own local edit stream.
+    /// Kept separate from `revision`, which also advances on remote
+    /// applies: peers rely on this sequence counting 0, 1, 2, ... with no
+    /// gaps to apply each other's ops in causal order, so it must not
+    /// jump just because we applied someone else's edit in between ours.
+    local_seq: u64,
+    unacked: Vec<OperationSeq>,
+    /// Next op sequence number expected from each peer, so their ops are
+    /// applied in the order that peer generated them even though gossip
+    /// delivers them in arbitrary order.
+    next_seq: HashMap<NodeId, u64>,
+    /// Ops that arrived before the op immediately preceding them (by
+    /// their origin's own sequence), held until the gap closes.
+    pending: HashMap<(NodeId, u64), OperationSeq>,
+}
+
+impl Default for SharedDocument {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SharedDocument {
+    pub fn new() -> Self {
+        Self {
+            text: String::new(),
+            revision: 0,
+            local_seq: 0,
+            unacked: Vec::new(),
+            next_seq: HashMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Applies an op generated locally, against our own current text, and
+    /// remembers it as unacknowledged in case a concurrent remote op needs
+    /// to be transformed against it. Returns the index of `op` within our
+    /// own local edit stream, for the caller to stamp onto the
+    /// `Message::Edit` it broadcasts.
+    pub fn apply_local(&mut self, op: OperationSeq) -> Result<u64, anyhow::Error> {
+        self.text = op.apply(&self.text)?;
+        self.unacked.push(op);
+        if self.unacked.len() > MAX_UNACKED {
+            self.unacked.remove(0);
+        }
+        self.revision += 1;
+        let seq = self.local_seq;
+        self.local_seq += 1;
+        Ok(seq)
+    }
+
+    /// Applies the `seq`th op peer `from` ever generated. If it arrived
+    /// ahead of its predecessor it's buffered until `seq` becomes the next
+    /// one we're expecting from `from`, instead of being transformed
+    /// against the wrong base and silently dropped when `op.apply` rejects
+    /// it (or, worse, applied out of order and corrupting the document).
+    pub fn apply_remote(
+        &mut self,
+        from: NodeId,
+        seq: u64,
+        op: OperationSeq,
+    ) -> Result<(), anyhow::Error> {
+        let expected = self.next_seq.get(&from).copied().unwrap_or(0);
+        if seq < expected {
+            return Ok(()); // already applied; a duplicate gossip delivery
+        }
+        if seq > expected {
+            let pending_from = self.pending.keys().filter(|(node, _)| *node == from).count();
+            if pending_from < MAX_PENDING_PER_PEER {
+                self.pending.insert((from, seq), op);
+            }
+            return Ok(());
+        }
+
+        self.apply_in_order(from, seq, op)?;
+        // the gap closing may have unblocked a run of already-buffered ops
+        let mut next = seq + 1;
+        while let Some(op) = self.pending.remove(&(from, next)) {
+            self.apply_in_order(from, next, op)?;
+            next += 1;
+        }
+        Ok(())
+    }
+
+    fn apply_in_order(&mut self, from: NodeId, seq: u64, mut op: OperationSeq) -> Result<(), anyhow::Error> {
+        for local_op in &mut self.unacked {
+            let (local_prime, op_prime) = local_op.transform(&op)?;
+            *local_op = local_prime;
+            op = op_prime;
+        }
+        self.text = op.apply(&self.text)?;
+        self.revision += 1;
+        self.next_seq.insert(from, seq + 1);
+        Ok(())
+    }
+}
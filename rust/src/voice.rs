@@ -0,0 +1,275 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use audiopus::{Application, Channels, SampleRate, coder::Decoder, coder::Encoder};
+use iroh::endpoint::{Connection, RecvStream, SendStream};
+use iroh::protocol::{AcceptError, ProtocolHandler};
+use iroh::{Endpoint, NodeId};
+use n0_future::boxed::BoxFuture;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::{Mutex, OnceCell};
+
+/// ALPN for the dedicated voice bi-stream, separate from `iroh_gossip::ALPN`
+/// since voice frames are per-peer and latency sensitive, unlike gossip's
+/// broadcast-to-everyone chat traffic.
+pub const VOICE_ALPN: &[u8] = b"iroh-gossip-example/voice/0";
+
+const SAMPLE_RATE: SampleRate = SampleRate::Hz48000;
+/// 20ms of mono audio at 48kHz.
+const FRAME_SAMPLES: usize = 960;
+/// How many out-of-order frames we're willing to hold while waiting for a
+/// gap to fill before giving up on it.
+const JITTER_CAPACITY: usize = 6;
+
+/// One fully decoded voice frame ready for playback, tagged with who sent it.
+pub struct VoiceFrame {
+    pub from: NodeId,
+    pub samples: Vec<f32>,
+}
+
+/// Reorders arriving frames by sequence number and drops the ones that
+/// showed up too late to matter, trading a little latency for smoother
+/// playback than applying frames in arrival order would give us.
+struct JitterBuffer {
+    next_seq: u32,
+    pending: BTreeMap<u32, Vec<f32>>,
+}
+
+impl JitterBuffer {
+    fn new() -> Self {
+        Self {
+            next_seq: 0,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    fn push(&mut self, seq: u32, frame: Vec<f32>) {
+        if seq < self.next_seq {
+            return; // arrived too late, drop it
+        }
+        self.pending.insert(seq, frame);
+        if self.pending.len() > JITTER_CAPACITY {
+            // waited long enough for the gap to fill; give up on it and jump
+            // the cursor to the oldest frame we're still holding
+            self.next_seq = *self.pending.keys().next().unwrap();
+        }
+    }
+
+    fn drain_ready(&mut self) -> Vec<Vec<f32>> {
+        let mut ready = Vec::new();
+        while let Some(frame) = self.pending.remove(&self.next_seq) {
+            ready.push(frame);
+            self.next_seq += 1;
+        }
+        ready
+    }
+}
+
+/// Encodes the mono 48kHz/20ms Opus stream we send, shared across every
+/// peer since it's always the same locally captured audio going out.
+/// Decoding is *not* here: an Opus decoder carries inter-frame state for
+/// the one stream it's decoding, so unlike the encoder it can't be shared
+/// across multiple senders - see `spawn_receiver`, which gives each peer
+/// its own.
+struct VoiceCodec {
+    encoder: std::sync::Mutex<Encoder>,
+}
+
+impl VoiceCodec {
+    fn new() -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            encoder: std::sync::Mutex::new(Encoder::new(
+                SAMPLE_RATE,
+                Channels::Mono,
+                Application::Voip,
+            )?),
+        })
+    }
+
+    fn encode(&self, pcm: &[f32]) -> Result<Vec<u8>, anyhow::Error> {
+        let mut out = vec![0u8; 4000];
+        let len = self.encoder.lock().unwrap().encode_float(pcm, &mut out)?;
+        out.truncate(len);
+        Ok(out)
+    }
+}
+
+/// Decodes one Opus payload through `decoder`, which must be the same
+/// `Decoder` instance used for every prior frame on this peer's stream -
+/// Opus's inter-frame prediction/PLC state is per-stream, so decoding
+/// frames from different senders through one shared decoder corrupts the
+/// reconstructed PCM for both.
+fn decode_frame(decoder: &mut Decoder, payload: &[u8]) -> Result<Vec<f32>, anyhow::Error> {
+    let mut pcm = vec![0f32; FRAME_SAMPLES];
+    let len = decoder.decode_float(Some(payload), &mut pcm, false)?;
+    pcm.truncate(len);
+    Ok(pcm)
+}
+
+async fn write_frame(stream: &mut SendStream, seq: u32, payload: &[u8]) -> Result<(), anyhow::Error> {
+    // little-endian `[seq: u32][opus payload]`, itself length-prefixed so
+    // the receiver can pull exactly one frame at a time off the stream
+    let mut frame = Vec::with_capacity(4 + payload.len());
+    frame.extend_from_slice(&seq.to_le_bytes());
+    frame.extend_from_slice(payload);
+    stream.write_all(&(frame.len() as u32).to_le_bytes()).await?;
+    stream.write_all(&frame).await?;
+    Ok(())
+}
+
+async fn read_frame(stream: &mut RecvStream) -> Result<(u32, Vec<u8>), anyhow::Error> {
+    let mut len_buf = [0u8; 4];
+    stream.read_exact(&mut len_buf).await?;
+    let mut frame = vec![0u8; u32::from_le_bytes(len_buf) as usize];
+    stream.read_exact(&mut frame).await?;
+    if frame.len() < 4 {
+        anyhow::bail!("voice frame too short to contain a sequence number: {} bytes", frame.len());
+    }
+    let seq = u32::from_le_bytes(frame[..4].try_into()?);
+    Ok((seq, frame[4..].to_vec()))
+}
+
+/// The outbound half of a voice connection to one peer: a bi-stream plus the
+/// sequence counter for frames we send on it.
+struct VoicePeer {
+    send: Mutex<SendStream>,
+    seq: AtomicU32,
+}
+
+/// Per-room voice subsystem: dials/accepts a dedicated QUIC bi-stream per
+/// peer on [`VOICE_ALPN`], encodes/decodes Opus over it, and reassembles
+/// incoming frames with a small jitter buffer before handing them back as
+/// [`VoiceFrame`]s.
+#[derive(Clone)]
+pub struct VoiceSession {
+    // set once `create_room`'s spawned task finishes binding its `Endpoint`;
+    // `VoiceSession` itself is built up front so it can be registered with
+    // the room's `Router` and stashed on the `RoomHandle` before that happens
+    endpoint: Arc<OnceCell<Endpoint>>,
+    codec: Arc<VoiceCodec>,
+    peers: Arc<Mutex<HashMap<NodeId, Arc<VoicePeer>>>>,
+    frame_tx: tokio::sync::mpsc::Sender<VoiceFrame>,
+}
+
+impl VoiceSession {
+    pub fn new(frame_tx: tokio::sync::mpsc::Sender<VoiceFrame>) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            endpoint: Arc::new(OnceCell::new()),
+            codec: Arc::new(VoiceCodec::new()?),
+            peers: Arc::new(Mutex::new(HashMap::new())),
+            frame_tx,
+        })
+    }
+
+    pub fn set_endpoint(&self, endpoint: Endpoint) {
+        // ignore failure: it just means we're already bound
+        let _ = self.endpoint.set(endpoint);
+    }
+
+    /// Dials `node_id` on the voice ALPN and starts decoding frames from it,
+    /// if we haven't already. Fire-and-forget: called whenever presence
+    /// tells us about a peer, so a single dial failure shouldn't stop the
+    /// rest of the room from talking.
+    ///
+    /// Both peers learn about each other's presence at roughly the same
+    /// time, so both would otherwise call `connect_peer` on each other and
+    /// end up with two bi-streams for the same pair. Only the peer with the
+    /// lower `NodeId` dials; the other side just waits to `accept` the
+    /// incoming connection, so exactly one stream ever gets opened per pair.
+    pub fn connect_peer(&self, node_id: NodeId) {
+        let this = self.clone();
+        tokio::spawn(async move {
+            if this.peers.lock().await.contains_key(&node_id) {
+                return;
+            }
+            let endpoint = this.endpoint.wait().await;
+            if endpoint.node_id() >= node_id {
+                return;
+            }
+            let Ok(connection) = endpoint.connect(node_id, VOICE_ALPN).await else {
+                return;
+            };
+            let Ok((send, recv)) = connection.open_bi().await else {
+                return;
+            };
+            this.register_peer(node_id, send, recv).await;
+        });
+    }
+
+    /// Registers a bi-stream for `node_id`, unless we already have one -
+    /// the ordering in `connect_peer` should make that impossible, but a
+    /// belated dial racing an `accept` is cheap to guard against, and
+    /// overwriting a live entry would drop a `SendStream` mid-use and leak
+    /// a duplicate receiver task feeding `frame_tx`.
+    async fn register_peer(&self, node_id: NodeId, send: SendStream, recv: RecvStream) {
+        let mut peers = self.peers.lock().await;
+        if peers.contains_key(&node_id) {
+            return;
+        }
+        peers.insert(
+            node_id,
+            Arc::new(VoicePeer {
+                send: Mutex::new(send),
+                seq: AtomicU32::new(0),
+            }),
+        );
+        drop(peers);
+        self.spawn_receiver(node_id, recv);
+    }
+
+    fn spawn_receiver(&self, from: NodeId, mut recv: RecvStream) {
+        let frame_tx = self.frame_tx.clone();
+        tokio::spawn(async move {
+            // one decoder per peer: its inter-frame state is per-stream,
+            // so it can't be shared with any other sender's receiver task
+            let Ok(mut decoder) = Decoder::new(SAMPLE_RATE, Channels::Mono) else {
+                return;
+            };
+            let mut jitter = JitterBuffer::new();
+            while let Ok((seq, payload)) = read_frame(&mut recv).await {
+                let Ok(samples) = decode_frame(&mut decoder, &payload) else {
+                    continue;
+                };
+                jitter.push(seq, samples);
+                for samples in jitter.drain_ready() {
+                    if frame_tx.send(VoiceFrame { from, samples }).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Encodes one locally captured frame and fans it out to every peer
+    /// we've already got a voice stream open with.
+    pub async fn push_frame(&self, pcm: &[f32]) -> Result<(), anyhow::Error> {
+        let payload = self.codec.encode(pcm)?;
+        let peers: Vec<_> = self.peers.lock().await.values().cloned().collect();
+        for peer in peers {
+            let seq = peer.seq.fetch_add(1, Ordering::Relaxed);
+            let mut send = peer.send.lock().await;
+            // best-effort, like the gossip broadcast loop: one dead peer
+            // shouldn't stop frames reaching everyone else
+            let _ = write_frame(&mut send, seq, &payload).await;
+        }
+        Ok(())
+    }
+}
+
+impl ProtocolHandler for VoiceSession {
+    fn accept(&self, connection: Connection) -> BoxFuture<Result<(), AcceptError>> {
+        let this = self.clone();
+        Box::pin(async move {
+            let node_id = connection
+                .remote_node_id()
+                .map_err(AcceptError::from_err)?;
+            let (send, recv) = connection
+                .accept_bi()
+                .await
+                .map_err(AcceptError::from_err)?;
+            this.register_peer(node_id, send, recv).await;
+            Ok(())
+        })
+    }
+}